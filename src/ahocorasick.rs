@@ -1,6 +1,10 @@
 use std::collections::{
     VecDeque,
 };
+use std::io::{
+    self,
+    Read,
+};
 
 use crate::{
     Match,
@@ -8,26 +12,153 @@ use crate::{
     Pattern,
 };
 
+/// Size, in bytes, of the reusable buffer a [`FindStream`] reads a source into.
+const STREAM_BUF_SIZE: usize = 8 * 1024;
+
 const FAIL: usize = 0;
 const DEAD: usize = 1;
 const START: usize = 2;
 
+/// Selects how an [`AhoCorasick`] automaton resolves multiple matches.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MatchKind {
+    /// Report every match, including those that overlap one another.
+    Standard,
+    /// Prefer the pattern that was added first when several match at the same position.
+    LeftmostFirst,
+    /// Prefer the longest match starting at a given position.
+    LeftmostLongest,
+}
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+    impl Sealed for usize {}
+}
+
+/// A state id representable in an [`AhoCorasick`] automaton's internal tables.
+///
+/// Sealed to `u8`, `u16`, `u32`, and `usize`: the width of `S` is what a `Node`'s
+/// `transitions` and `fail` fields cost per entry, so narrower types shrink the automaton,
+/// at the cost of a lower ceiling on how many states it can hold.
+pub trait StateID: sealed::Sealed + Copy + Eq + std::fmt::Debug {
+    /// Number of distinct states this type can address.
+    const MAX_STATES: usize;
+
+    fn from_usize(value: usize) -> Self;
+    fn to_usize(self) -> usize;
+}
+
+impl StateID for u8 {
+    const MAX_STATES: usize = u8::MAX as usize + 1;
+    fn from_usize(value: usize) -> Self { value as u8 }
+    fn to_usize(self) -> usize { self as usize }
+}
+
+impl StateID for u16 {
+    const MAX_STATES: usize = u16::MAX as usize + 1;
+    fn from_usize(value: usize) -> Self { value as u16 }
+    fn to_usize(self) -> usize { self as usize }
+}
+
+impl StateID for u32 {
+    const MAX_STATES: usize = u32::MAX as usize + 1;
+    fn from_usize(value: usize) -> Self { value as u32 }
+    fn to_usize(self) -> usize { self as usize }
+}
+
+impl StateID for usize {
+    // No automaton will ever hold usize::MAX states in practice, but this is the type's
+    // true ceiling and every smaller width is tried first anyway (see `AnyAhoCorasick`).
+    const MAX_STATES: usize = usize::MAX;
+    fn from_usize(value: usize) -> Self { value }
+    fn to_usize(self) -> usize { self }
+}
+
+/// Error returned by [`AhoCorasick::try_new`] when a trie needs more states than the
+/// chosen [`StateID`] type can address.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TooManyStates {
+    /// Number of states reached when the first one that did not fit was added; the trie
+    /// needs at least this many.
+    pub len: usize,
+    /// Largest state count the chosen `StateID` type can represent.
+    pub max: usize,
+}
+
+impl std::fmt::Display for TooManyStates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "trie needs at least {} states, but the chosen state id type can only address {}", self.len, self.max)
+    }
+}
+
+impl std::error::Error for TooManyStates {}
+
+/// Maps each of the 256 byte values down to a smaller equivalence class.
+///
+/// Bytes that never appear in any pattern can never distinguish one transition from
+/// another, so they are folded into a single default class. Transition rows are then
+/// indexed by class rather than by raw byte, which shrinks a `Node`'s transition table
+/// from 256 entries to [`ByteClasses::len`].
+#[derive(Clone, Debug)]
+pub struct ByteClasses {
+    classes: [usize; 256],
+    len: usize,
+}
+
+impl ByteClasses {
+    /// Build classes from the bytes that actually appear in `patterns`.
+    fn new(patterns: &[Pattern]) -> Self {
+        let mut significant = [false; 256];
+        for pattern in patterns {
+            for &byte in pattern.value {
+                significant[byte as usize] = true;
+            }
+        }
+
+        // Class 0 is the default class, shared by every byte that is not significant.
+        let mut classes = [0usize; 256];
+        let mut next_class = 1;
+        for (byte, &is_significant) in significant.iter().enumerate() {
+            if is_significant {
+                classes[byte] = next_class;
+                next_class += 1;
+            }
+        }
+
+        Self { classes, len: next_class }
+    }
+
+    /// Return the equivalence class for `byte`.
+    #[inline]
+    fn get(&self, byte: u8) -> usize {
+        self.classes[byte as usize]
+    }
+
+    /// Return the number of distinct classes, including the default class.
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
 /// Automaton node.
-#[derive(Clone)]
-pub struct Node {
+#[derive(Clone, Debug)]
+pub struct Node<S: StateID> {
     /// Patterns match by this node.
     pub matches: Vec<Match>,
-    /// Transitions to other node.
-    pub transitions: [usize; 256],
+    /// Transitions to other node, indexed by byte class rather than raw byte.
+    pub transitions: Vec<S>,
     /// Fail transition id.
-    pub fail: usize,
+    pub fail: S,
     /// Distance from START.
     pub depth: usize,
 }
 
-impl Node {
-    pub fn new(fail: usize, depth: usize) -> Self {
-        return Self{ matches: Vec::new(), transitions: [0; 256], fail, depth }
+impl<S: StateID> Node<S> {
+    pub fn new(fail: S, depth: usize, classes: usize) -> Self {
+        Self{ matches: Vec::new(), transitions: vec![S::from_usize(FAIL); classes], fail, depth }
     }
 
     /// Return the length of the longest match.
@@ -35,7 +166,7 @@ impl Node {
     /// because any subsequent match is one from a fail transition, which points to a suffix.
     /// Returns None if the node has no matches.
     pub fn get_longest_match_len(&self) -> Option<usize> {
-        self.matches.get(0).map(|p| p.pattern_len)
+        self.matches.first().map(|p| p.pattern_len)
     }
 }
 
@@ -47,30 +178,57 @@ struct Position {
     depth_longest_match: Option<usize>,
 }
 
-// NOTE: Anything marked *LL is for leftmost-longest match semantics.
+// NOTE: Anything marked *LL is for leftmost-longest match semantics, and *LF is for
+// leftmost-first match semantics. Standard match semantics need neither: fail transitions
+// are left pointing at the true longest-proper-suffix state so matches can overlap.
 
-/// Aho-Corasick with leftmost-longest match semantics.
-#[derive(Clone)]
-pub struct LeftmostLongest {
-    nodes: Vec<Node>,
+/// Aho-Corasick automaton, parameterized at build time by [`MatchKind`] and by the
+/// [`StateID`] type `S` used to store node ids. `S` defaults to `usize`; pick a narrower
+/// type (or build via [`AnyAhoCorasick`]) to shrink per-node storage once the state count
+/// is known to fit.
+#[derive(Clone, Debug)]
+pub struct AhoCorasick<S: StateID = usize> {
+    nodes: Vec<Node<S>>,
+    classes: ByteClasses,
+    kind: MatchKind,
+    anchored: bool,
 }
 
-impl LeftmostLongest {
-    /// Return a new AhoCorasick automaton with leftmost-longest
-    /// match semantics.
-    pub fn new(patterns: &[Pattern]) -> Self {
-        let mut ll = Self { nodes: Vec::new() };
-        ll.build_trie(patterns);
-        ll.encode_start_to_start();
-        ll.encode_dead_to_dead();
-        ll.encode_trie_failure();
-        if ll.nodes[START].matches.len() > 0 {
-            ll.encode_start_to_dead();
+impl<S: StateID> AhoCorasick<S> {
+    /// Return a new AhoCorasick automaton with the given match semantics, or
+    /// [`TooManyStates`] if the trie needs more states than `S` can address.
+    ///
+    /// For [`MatchKind::LeftmostFirst`], `patterns` order matters: when two patterns would
+    /// match at the same start position, the one that appears earlier in `patterns` wins.
+    ///
+    /// When `anchored` is `true`, START fails directly to DEAD instead of looping back to
+    /// itself, so the search halts the moment the prefix diverges rather than skipping ahead
+    /// to try matching later in the haystack; pair this with [`AhoCorasick::find_anchored`].
+    pub fn try_new(kind: MatchKind, anchored: bool, patterns: &[Pattern]) -> Result<Self, TooManyStates> {
+        let mut ac = Self { nodes: Vec::new(), classes: ByteClasses::new(patterns), kind, anchored };
+        ac.build_trie(patterns)?;
+        if anchored {
+            ac.encode_start_to_dead_on_fail();
+        } else {
+            ac.encode_start_to_start();
+        }
+        ac.encode_dead_to_dead();
+        ac.encode_trie_failure();
+        if anchored {
+            ac.encode_fail_chain_to_dead();
         }
-        ll
+        if ac.kind != MatchKind::Standard && !ac.nodes[START].matches.is_empty() {
+            ac.encode_start_to_dead();
+        }
+        Ok(ac)
     }
 
     /// Return the [`Location`] of the next [`Match`] in the haystack from start_byte_index.
+    ///
+    /// For [`MatchKind::Standard`], this is only the FIRST match recorded at the first state
+    /// reached that has any match at all; a state can carry more than one (e.g. "he" riding
+    /// along on the "she" state via a fail transition), and this call does not enumerate them.
+    /// Use [`AhoCorasick::find_iter`] to see every match, including those.
     pub fn find(&self, haystack: &[u8], mut start_byte_index: usize) -> Option<Location> {
         let mut last_location = self.get_location(START, 0, start_byte_index);
         let mut current_node_id: usize = START;
@@ -79,21 +237,54 @@ impl LeftmostLongest {
             debug_assert_ne!(current_node_id, FAIL);
             start_byte_index += 1;
             if current_node_id == DEAD {
-                debug_assert_ne!(last_location, None);
+                // In an anchored automaton, START can fail straight to DEAD on the very
+                // first byte that cannot extend any pattern, so DEAD does not imply a match
+                // was already found the way it does for the unanchored leftmost modes.
                 return last_location;
             }
             let location = self.get_location(current_node_id, 0, start_byte_index);
             if location.is_some() {
+                // Standard semantics never treat landing on a match state as terminal the
+                // way the DEAD-reroute does for the leftmost modes: the automaton is free to
+                // keep running past it (an overlapping iterator will visit it again), but this
+                // call reports the match the moment it ends rather than waiting for a longer one.
+                if self.kind == MatchKind::Standard {
+                    return location;
+                }
+                if self.kind == MatchKind::LeftmostFirst && last_location.is_some() {
+                    // The first match found along this walk was inserted no later than
+                    // anything reachable afterwards without a change of start position (a
+                    // straight extension like "ab" -> "abc" only ever deepens the same
+                    // start), so once one is found nothing further down this same walk can
+                    // outrank it; the dead-routing below already guarantees the walk cannot
+                    // continue past the point where an earlier-starting match could still
+                    // turn up (see the *LF branch of `encode_trie_failure`).
+                    continue;
+                }
                 last_location = location;
             }
         }
         last_location
     }
 
-    /// Return a [`Location`] for a node id and match id with end index.
+    /// Return the match beginning exactly at `at`, or `None` if no pattern starts there.
+    ///
+    /// Unlike [`AhoCorasick::find`], this does not scan forward: the automaton must have
+    /// been built with `anchored: true`, which makes START fail straight to DEAD on any
+    /// byte that cannot extend a pattern, halting the search instead of skipping ahead.
+    pub fn find_anchored(&self, haystack: &[u8], at: usize) -> Option<Location> {
+        debug_assert!(self.anchored, "find_anchored requires an automaton built with anchored: true");
+        self.find(haystack, at)
+    }
+
+    /// Return a [`Location`] for the `r#match`-th match recorded at a node id, with end index.
+    ///
+    /// A node can carry more than one match (a shorter pattern riding along via a fail
+    /// transition, e.g. "he" on the "she" state): `r#match` selects which one, so a caller
+    /// that wants every match recorded here can walk `0..` until this returns `None`.
     fn get_location(&self, id: usize, r#match: usize, end: usize) -> Option<Location> {
         let node = &self.nodes[id];
-        if node.matches.len() == 0 || r#match >= node.matches.len() {
+        if r#match >= node.matches.len() {
             return None;
         }
         let match_node = node.matches[r#match];
@@ -102,12 +293,13 @@ impl LeftmostLongest {
 
     /// Return the next non-fail node id.
     fn get_next_non_fail_node_id(&self, mut id: usize, byte: u8) -> usize {
+        let class = self.classes.get(byte);
         loop {
-            let next = self.nodes[id].transitions[byte as usize];
+            let next = self.nodes[id].transitions[class].to_usize();
             if next != FAIL {
                 return next
             } else {
-                id = self.nodes[id].fail;
+                id = self.nodes[id].fail.to_usize();
             }
         }
     }
@@ -117,11 +309,11 @@ impl LeftmostLongest {
     //<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
 
     /// Build a trie with a node for each byte in patterns.
-    fn build_trie(&mut self, patterns: &[Pattern]) {
+    fn build_trie(&mut self, patterns: &[Pattern]) -> Result<(), TooManyStates> {
         // These are the initial states.
         // FAIL, DEAD, START.
         for _ in 0..3 {
-            self.add_node(0);
+            self.add_node(0)?;
         }
         // For each pattern, create a chain of nodes ending with a leaf node containing a match.
         for pattern in patterns.iter() {
@@ -129,11 +321,12 @@ impl LeftmostLongest {
             // Iterate over pattern to create a transition to each character from START.
             for (depth, byte) in pattern.value.iter().enumerate() {
                 let depth_non_zero_index = depth + 1;
-                let current_node_transition_id = self.nodes[current_node_id].transitions[*byte as usize];
+                let class = self.classes.get(*byte);
+                let current_node_transition_id = self.nodes[current_node_id].transitions[class].to_usize();
                 if current_node_transition_id == FAIL {
                     // Add a transition for the byte.
-                    let new_node_id = self.add_node(depth_non_zero_index);
-                    self.nodes[current_node_id].transitions[*byte as usize] = new_node_id;
+                    let new_node_id = self.add_node(depth_non_zero_index)?;
+                    self.nodes[current_node_id].transitions[class] = S::from_usize(new_node_id);
                     current_node_id = new_node_id;
                 } else {
                     // Transition already exists, so just move to it.
@@ -142,35 +335,69 @@ impl LeftmostLongest {
             }
 
             // Found the end of the branch for this pattern.
-            // Record the match.
+            // Record the match. Patterns earlier in the slice are pushed first, so
+            // LeftmostFirst can rely on matches[0] being the earliest-inserted pattern.
             let m = Match { pattern_id: pattern.id, pattern_len: pattern.value.len() };
             self.nodes[current_node_id].matches.push(m);
         }
+        Ok(())
     }
 
-    /// Encode START->FAIL transitions as START->START.
+    /// Encode START->FAIL transitions as START->START, one per byte class.
     fn encode_start_to_start(&mut self) {
-        for byte in 0..256 {
-            if self.nodes[START].transitions[byte] == FAIL {
-                self.nodes[START].transitions[byte] = START;
+        for class in 0..self.classes.len() {
+            if self.nodes[START].transitions[class].to_usize() == FAIL {
+                self.nodes[START].transitions[class] = S::from_usize(START);
             }
         }
     }
 
-    /// Encode DEAD->FAIL transitions as DEAD->DEAD.
+    /// Encode DEAD->FAIL transitions as DEAD->DEAD, one per byte class.
     fn encode_dead_to_dead(&mut self) {
-        for byte in 0..256 {
-            if self.nodes[DEAD].transitions[byte] == FAIL {
-                self.nodes[DEAD].transitions[byte] = DEAD;
+        for class in 0..self.classes.len() {
+            if self.nodes[DEAD].transitions[class].to_usize() == FAIL {
+                self.nodes[DEAD].transitions[class] = S::from_usize(DEAD);
             }
         }
     }
 
-    /// Encode START->START transitions as START->DEAD.
+    /// Encode START->START transitions as START->DEAD, one per byte class.
     fn encode_start_to_dead(&mut self) {
-        for byte in 0..256 {
-            if self.nodes[START].transitions[byte] == START {
-                self.nodes[START].transitions[byte] = DEAD;
+        for class in 0..self.classes.len() {
+            if self.nodes[START].transitions[class].to_usize() == START {
+                self.nodes[START].transitions[class] = S::from_usize(DEAD);
+            }
+        }
+    }
+
+    /// Encode START->FAIL transitions as START->DEAD, one per byte class.
+    ///
+    /// Used for anchored automatons: a byte that cannot extend any pattern from START
+    /// should halt the search immediately instead of restarting it one byte later.
+    fn encode_start_to_dead_on_fail(&mut self) {
+        for class in 0..self.classes.len() {
+            if self.nodes[START].transitions[class].to_usize() == FAIL {
+                self.nodes[START].transitions[class] = S::from_usize(DEAD);
+            }
+        }
+    }
+
+    /// Encode every node's remaining FAIL transitions as DEAD, one per byte class.
+    ///
+    /// Must run after [`AhoCorasick::encode_trie_failure`], which is what leaves a node's
+    /// transitions FAIL wherever it has no explicit child for a byte. At search time such a
+    /// cell would otherwise be resolved by following the node's fail pointer to a suffix
+    /// state -- but that suffix state represents a match starting later in the haystack
+    /// than wherever this walk began, which an anchored automaton must never report. So for
+    /// anchored automatons, a missing explicit continuation ends the search right there
+    /// instead of being chased through the fail chain.
+    fn encode_fail_chain_to_dead(&mut self) {
+        let width = self.classes.len();
+        for node in self.nodes.iter_mut() {
+            for class in 0..width {
+                if node.transitions[class].to_usize() == FAIL {
+                    node.transitions[class] = S::from_usize(DEAD);
+                }
             }
         }
     }
@@ -179,33 +406,37 @@ impl LeftmostLongest {
     fn encode_trie_failure(&mut self) {
         let mut queue: VecDeque<Position> = VecDeque::new();
 
-        for byte in 0..256 {
-            let start_node = &mut &self.nodes[START];
-            let transition_id = start_node.transitions[byte as usize];
-            // Avoid infinite loop...
-            if transition_id == START {
+        for class in 0..self.classes.len() {
+            let start_node = &self.nodes[START];
+            let transition_id = start_node.transitions[class].to_usize();
+            // Avoid infinite loop... START self-loops on unanchored automatons, and DEAD is
+            // the immediate-halt sentinel an anchored automaton's START fails to instead.
+            if transition_id == START || transition_id == DEAD {
                 continue;
             }
-            let match_depth: Option<usize> = if start_node.matches.len() > 0 {
+            let match_depth: Option<usize> = if !start_node.matches.is_empty() {
                 Some(0)
             } else {
                 None
             };
             queue.push_back(Position{ id: transition_id, depth_longest_match: match_depth });
 
-            // *LL
-            // In leftmost-longest, failure transitions to DEAD instead of START.
-            let next_node = &mut self.nodes[transition_id];
-            if next_node.matches.len() > 0 {
-                next_node.fail = DEAD;
+            // *LL *LF
+            // In the leftmost modes, a pattern of length 1 fails to DEAD instead of START so it
+            // cannot be re-matched starting one byte later.
+            if self.kind != MatchKind::Standard {
+                let next_node = &mut self.nodes[transition_id];
+                if !next_node.matches.is_empty() {
+                    next_node.fail = S::from_usize(DEAD);
+                }
             }
         }
 
         // Traverse queue to find additional transitions.
         while let Some(position) = queue.pop_front() {
             let prev = queue.len();
-            for byte in 0..256 {
-                let next_id = self.nodes[position.id].transitions[byte];
+            for class in 0..self.classes.len() {
+                let next_id = self.nodes[position.id].transitions[class].to_usize();
                 // If it does not transition to anything, skip it.
                 if next_id == FAIL {
                     continue;
@@ -215,7 +446,7 @@ impl LeftmostLongest {
                 // Establish depth of match, if any. None if no match exists.
                 let next_match_depth = match position.depth_longest_match {
                     Some(depth) => Some(depth),
-                    _ if transition_node.matches.len() > 0 => {
+                    _ if !transition_node.matches.is_empty() => {
                         Some(transition_node.depth - transition_node.get_longest_match_len().unwrap() + 1)
                     }
                     None => None,
@@ -224,25 +455,34 @@ impl LeftmostLongest {
 
                 // Figure out what this falls back to.
                 let fail_id = {
-                    let mut fail_id = self.nodes[position.id].fail;
-                    while self.nodes[position.id].transitions[byte] == FAIL {
-                        fail_id = self.nodes[position.id].fail;
+                    let mut fail_id = self.nodes[position.id].fail.to_usize();
+                    while self.nodes[position.id].transitions[class].to_usize() == FAIL {
+                        fail_id = self.nodes[position.id].fail.to_usize();
                     }
-                    self.nodes[fail_id].transitions[byte]
+                    self.nodes[fail_id].transitions[class].to_usize()
                 };
 
-                if let Some(match_depth) = next_match_depth {
-                    let fail_depth = self.nodes[fail_id].depth;
-                    let next_depth = self.nodes[next_id].depth;
-                    if next_depth - match_depth + 1 > fail_depth {
-                        self.nodes[next_id].fail = DEAD;
-                        continue;
+                if self.kind == MatchKind::LeftmostLongest || self.kind == MatchKind::LeftmostFirst {
+                    // *LL *LF
+                    // Once a match is active along this path, the fail chain can only ever
+                    // lead to a match starting at the SAME or a later position (a straight
+                    // walk down the trie never moves its start earlier), so whether it is
+                    // worth continuing past `next_id` depends only on whether the fail chain
+                    // could still extend the currently active match further. For *LL that
+                    // means comparing lengths; for *LF the active match already won by
+                    // virtue of being found first along this walk (see the corresponding
+                    // check in `find`), so any candidate from the fail chain loses outright.
+                    if let Some(match_depth) = next_match_depth {
+                        let fail_depth = self.nodes[fail_id].depth;
+                        let next_depth = self.nodes[next_id].depth;
+                        if self.kind == MatchKind::LeftmostFirst || next_depth - match_depth + 1 > fail_depth {
+                            self.nodes[next_id].fail = S::from_usize(DEAD);
+                            continue;
+                        }
+                        debug_assert_ne!(self.nodes[next_id].fail.to_usize(), START, "should never fail to start in leftmost configuration");
                     }
-
-                    // *LL
-                    debug_assert_ne!(self.nodes[next_id].fail, START, "should never fail to start in leftmost configuration");
                 }
-                self.nodes[next_id].fail = fail_id;
+                self.nodes[next_id].fail = S::from_usize(fail_id);
                 debug_assert!(fail_id != next_id);
 
                 // Shadow fail_id and next_id Node equivalents.
@@ -253,31 +493,344 @@ impl LeftmostLongest {
                     let (left, right) = self.nodes.split_at_mut(fail_id);
                     (&mut right[0], &mut left[next_id])
                 };
-                next_id.matches.extend_from_slice(&fail_id.matches);
+                // A fail-chain match starts later than this node's own walk, which an
+                // anchored automaton must never report.
+                if !self.anchored {
+                    next_id.matches.extend_from_slice(&fail_id.matches);
+                }
             }
 
             // If this is a match state with no transitions, set FAIL to DEAD to prevent it from restarting.
-            if queue.len() == prev && self.nodes[position.id].matches.len() > 0 {
-                self.nodes[position.id].fail = DEAD;
+            if queue.len() == prev && self.kind != MatchKind::Standard && !self.nodes[position.id].matches.is_empty() {
+                self.nodes[position.id].fail = S::from_usize(DEAD);
             }
-            // *LL
-            // A non leftmost-longest implementation may want to copy empty matches from the state state here,
-            // to support overlapping matches.
         }
     }
 
-    /// Add a Node and return its id.
-    fn add_node(&mut self, depth: usize) -> usize {
+    /// Add a Node and return its id, or [`TooManyStates`] if `S` cannot address it.
+    fn add_node(&mut self, depth: usize) -> Result<usize, TooManyStates> {
         let id = self.nodes.len();
+        if id >= S::MAX_STATES {
+            return Err(TooManyStates { len: id + 1, max: S::MAX_STATES });
+        }
         self.nodes.push(Node {
             depth,
-            fail: START,
-            transitions: [FAIL; 256],
+            fail: S::from_usize(START),
+            transitions: vec![S::from_usize(FAIL); self.classes.len()],
             matches: vec![],
         });
-        id
+        Ok(id)
+    }
+
+    /// Return an iterator over matches in `reader`, without reading it entirely into memory.
+    ///
+    /// This reads `reader` into a reusable buffer and runs the automaton byte-by-byte,
+    /// carrying `current_node_id` across refills: because Aho-Corasick state only depends
+    /// on the current node and not on retained bytes, the node can cross a buffer boundary
+    /// even though the bytes behind it are gone. A running absolute offset is kept alongside
+    /// it so [`StreamMatch::end`] is relative to the whole stream rather than the buffer.
+    pub fn find_stream<R: Read>(&self, reader: R) -> FindStream<'_, R, S> {
+        FindStream {
+            ac: self,
+            reader,
+            buf: vec![0u8; STREAM_BUF_SIZE],
+            filled: 0,
+            pos: 0,
+            offset: 0,
+            current_node_id: START,
+            match_index: 0,
+            pending: None,
+            done: false,
+        }
+    }
+
+    /// Return an allocation-free iterator over every match in `haystack`, in order.
+    pub fn find_iter<'a>(&'a self, haystack: &'a [u8]) -> FindIter<'a, S> {
+        FindIter { ac: self, haystack, at: 0, node_id: START, match_index: 0 }
+    }
+} // impl AhoCorasick
+
+/// Iterator over every [`Match`] in a haystack, returned by [`AhoCorasick::find_iter`].
+pub struct FindIter<'a, S: StateID = usize> {
+    ac: &'a AhoCorasick<S>,
+    haystack: &'a [u8],
+    at: usize,
+    /// Automaton state carried across calls, used only for [`MatchKind::Standard`]: true
+    /// overlapping search needs a single persistent walk rather than repeated calls to
+    /// `find` restarting at `START`, since restarting loses both extra matches recorded at
+    /// the node it lands on and any match whose start precedes the new cursor.
+    node_id: usize,
+    /// Index of the next match to yield at `node_id`, for [`MatchKind::Standard`].
+    match_index: usize,
+}
+
+impl<'a, S: StateID> Iterator for FindIter<'a, S> {
+    type Item = Location;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ac.kind == MatchKind::Standard {
+            return self.next_overlapping();
+        }
+        let location = self.ac.find(self.haystack, self.at)?;
+        // A zero-width match would otherwise leave `at` unchanged and loop forever.
+        self.at = if location.end == self.at { self.at + 1 } else { location.end };
+        Some(location)
     }
-} // impl LeftmostLongest
+}
+
+impl<'a, S: StateID> FindIter<'a, S> {
+    /// Drive the persistent walk used by [`MatchKind::Standard`]: yield every match recorded
+    /// at the current node before consuming another byte and transitioning to the next one.
+    fn next_overlapping(&mut self) -> Option<Location> {
+        loop {
+            if let Some(location) = self.ac.get_location(self.node_id, self.match_index, self.at) {
+                self.match_index += 1;
+                return Some(location);
+            }
+            self.match_index = 0;
+            if self.at >= self.haystack.len() {
+                return None;
+            }
+            self.node_id = self.ac.get_next_non_fail_node_id(self.node_id, self.haystack[self.at]);
+            debug_assert_ne!(self.node_id, FAIL);
+            self.at += 1;
+            if self.node_id == DEAD {
+                return None;
+            }
+        }
+    }
+}
+
+/// An [`AhoCorasick`] automaton built with the narrowest [`StateID`] type that can
+/// represent its node count, chosen automatically at build time.
+#[derive(Clone)]
+pub enum AnyAhoCorasick {
+    U8(AhoCorasick<u8>),
+    U16(AhoCorasick<u16>),
+    U32(AhoCorasick<u32>),
+    Usize(AhoCorasick<usize>),
+}
+
+impl AnyAhoCorasick {
+    /// Build an automaton, trying progressively wider [`StateID`] types until one fits.
+    pub fn new(kind: MatchKind, anchored: bool, patterns: &[Pattern]) -> Self {
+        if let Ok(ac) = AhoCorasick::<u8>::try_new(kind, anchored, patterns) {
+            return Self::U8(ac);
+        }
+        if let Ok(ac) = AhoCorasick::<u16>::try_new(kind, anchored, patterns) {
+            return Self::U16(ac);
+        }
+        if let Ok(ac) = AhoCorasick::<u32>::try_new(kind, anchored, patterns) {
+            return Self::U32(ac);
+        }
+        // No real trie exceeds what a usize state id can address.
+        Self::Usize(AhoCorasick::try_new(kind, anchored, patterns).expect("usize state ids cover any buildable trie"))
+    }
+
+    /// Return the [`Location`] of the next [`Match`] in the haystack from start_byte_index.
+    pub fn find(&self, haystack: &[u8], start_byte_index: usize) -> Option<Location> {
+        match self {
+            Self::U8(ac) => ac.find(haystack, start_byte_index),
+            Self::U16(ac) => ac.find(haystack, start_byte_index),
+            Self::U32(ac) => ac.find(haystack, start_byte_index),
+            Self::Usize(ac) => ac.find(haystack, start_byte_index),
+        }
+    }
+}
+
+/// A contiguous DFA compiled from an [`AhoCorasick`] automaton.
+///
+/// [`AhoCorasick::find`] walks the `fail` chain on every byte that has no explicit
+/// transition, which adds a loop per byte in the worst case. `Dfa` resolves every
+/// (state, byte class) pair ahead of time, so searching costs exactly one table lookup
+/// per input byte at the expense of higher build time and memory.
+#[derive(Clone)]
+pub struct Dfa {
+    /// Fully resolved transitions, indexed `[state][class]`.
+    transitions: Vec<Vec<usize>>,
+    /// Matches carried forward from the source automaton, indexed by state.
+    matches: Vec<Vec<Match>>,
+    classes: ByteClasses,
+    kind: MatchKind,
+}
+
+impl Dfa {
+    /// Compile a [`Dfa`] from an already-built [`AhoCorasick`] automaton.
+    pub fn new<S: StateID>(ac: &AhoCorasick<S>) -> Self {
+        let n = ac.nodes.len();
+        let width = ac.classes.len();
+        let mut transitions = vec![vec![FAIL; width]; n];
+        let mut matches = vec![Vec::new(); n];
+
+        // START and DEAD are not reached by any explicit trie transition, so seed them
+        // directly: their rows are already fully resolved self-loops from `encode_*`.
+        transitions[START] = ac.nodes[START].transitions.iter().map(|t| t.to_usize()).collect();
+        transitions[DEAD] = ac.nodes[DEAD].transitions.iter().map(|t| t.to_usize()).collect();
+        matches[START] = ac.nodes[START].matches.clone();
+        matches[DEAD] = ac.nodes[DEAD].matches.clone();
+
+        let mut seen = vec![false; n];
+        seen[START] = true;
+        seen[DEAD] = true;
+
+        // BFS over the trie's explicit transitions visits states in increasing depth
+        // order, so by the time a state is dequeued, `fail(state)` (which always has a
+        // strictly smaller depth) already has a fully resolved row to copy from.
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        queue.push_back(START);
+        while let Some(id) = queue.pop_front() {
+            for (class, &transition) in ac.nodes[id].transitions.iter().enumerate() {
+                let explicit = transition.to_usize();
+                if explicit != FAIL {
+                    transitions[id][class] = explicit;
+                    if !seen[explicit] {
+                        seen[explicit] = true;
+                        matches[explicit] = ac.nodes[explicit].matches.clone();
+                        queue.push_back(explicit);
+                    }
+                } else {
+                    transitions[id][class] = transitions[ac.nodes[id].fail.to_usize()][class];
+                }
+            }
+        }
+
+        Self { transitions, matches, classes: ac.classes.clone(), kind: ac.kind }
+    }
+
+    /// Return the [`Location`] of the next [`Match`] in the haystack from start_byte_index.
+    pub fn find(&self, haystack: &[u8], mut start_byte_index: usize) -> Option<Location> {
+        let mut last_location = self.get_location(START, start_byte_index);
+        let mut current_state = START;
+        while start_byte_index < haystack.len() {
+            let class = self.classes.get(haystack[start_byte_index]);
+            current_state = self.transitions[current_state][class];
+            start_byte_index += 1;
+            if current_state == DEAD {
+                // See the matching comment in `AhoCorasick::find`: an anchored automaton's
+                // START can fail straight to DEAD before any match is ever found.
+                return last_location;
+            }
+            let location = self.get_location(current_state, start_byte_index);
+            if location.is_some() {
+                if self.kind == MatchKind::Standard {
+                    return location;
+                }
+                if self.kind == MatchKind::LeftmostFirst && last_location.is_some() {
+                    // Same keep-first policy as `AhoCorasick::find`.
+                    continue;
+                }
+                last_location = location;
+            }
+        }
+        last_location
+    }
+
+    /// Return a [`Location`] for a state id with end index.
+    fn get_location(&self, id: usize, end: usize) -> Option<Location> {
+        self.matches[id].first().map(|&r#match| Location { r#match, end })
+    }
+}
+
+/// A [`Match`] found by [`FindStream`], positioned relative to the whole stream rather
+/// than the current buffer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StreamMatch {
+    pub r#match: Match,
+    /// Absolute index, within the whole stream, of the first byte after the match.
+    pub end: usize,
+}
+
+/// Streaming match iterator returned by [`AhoCorasick::find_stream`].
+pub struct FindStream<'a, R, S: StateID = usize> {
+    ac: &'a AhoCorasick<S>,
+    reader: R,
+    buf: Vec<u8>,
+    /// Number of valid bytes currently in `buf`.
+    filled: usize,
+    /// Cursor into `buf` of the next byte to feed the automaton.
+    pos: usize,
+    /// Absolute stream offset of `buf[0]`.
+    offset: usize,
+    current_node_id: usize,
+    /// Index of the next match to yield at `current_node_id`, before consuming more input.
+    /// Only used for [`MatchKind::Standard`]; the leftmost modes use `pending` instead.
+    match_index: usize,
+    /// The best match found so far along the current walk, for the leftmost modes. Like
+    /// `find`'s `last_location`, it is only surfaced once the walk ends (DEAD or
+    /// end-of-stream), never the moment a match state is merely touched.
+    pending: Option<StreamMatch>,
+    done: bool,
+}
+
+impl<'a, R: Read, S: StateID> Iterator for FindStream<'a, R, S> {
+    type Item = io::Result<StreamMatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.ac.kind == MatchKind::Standard {
+                // Drain every match recorded at the current node (e.g. "he" riding along
+                // on the "she" node via a fail transition) before consuming another byte.
+                if let Some(&r#match) = self.ac.nodes[self.current_node_id].matches.get(self.match_index) {
+                    self.match_index += 1;
+                    let end = self.offset + self.pos;
+                    return Some(Ok(StreamMatch { r#match, end }));
+                }
+                self.match_index = 0;
+            }
+
+            if self.done {
+                return None;
+            }
+            if self.pos >= self.filled {
+                self.offset += self.pos;
+                match self.reader.read(&mut self.buf) {
+                    Ok(0) => {
+                        self.done = true;
+                        if let Some(pending) = self.pending.take() {
+                            return Some(Ok(pending));
+                        }
+                        return None;
+                    }
+                    Ok(n) => {
+                        self.filled = n;
+                        self.pos = 0;
+                    }
+                    Err(err) => {
+                        self.done = true;
+                        return Some(Err(err));
+                    }
+                }
+                continue;
+            }
+
+            let byte = self.buf[self.pos];
+            self.current_node_id = self.ac.get_next_non_fail_node_id(self.current_node_id, byte);
+            self.pos += 1;
+            if self.current_node_id == DEAD {
+                // The leftmost modes route here once the best match starting at the
+                // current position is already behind us; surface it now, then resume
+                // scanning for the next match from START.
+                self.current_node_id = START;
+                if let Some(pending) = self.pending.take() {
+                    return Some(Ok(pending));
+                }
+                continue;
+            }
+            if self.ac.kind != MatchKind::Standard {
+                if let Some(&r#match) = self.ac.nodes[self.current_node_id].matches.first() {
+                    // Mirrors `find`'s overwrite policy: LeftmostFirst keeps the first
+                    // match found along this walk, since nothing reachable afterwards
+                    // without a change of start position can outrank it; LeftmostLongest
+                    // keeps taking the latest one, since the walk only ever deepens.
+                    if !(self.ac.kind == MatchKind::LeftmostFirst && self.pending.is_some()) {
+                        let end = self.offset + self.pos;
+                        self.pending = Some(StreamMatch { r#match, end });
+                    }
+                }
+            }
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -299,33 +852,240 @@ mod tests {
             Location { r#match: Match { pattern_id: 3, pattern_len: 2 }, end: 23 },
             Location { r#match: Match { pattern_id: 0, pattern_len: 2 }, end: 27 },
         ];
-        t(&patterns, haystack, &expected);
+        t(MatchKind::LeftmostLongest, &patterns, haystack, &expected);
     }
 
-    #[track_caller]
-    fn t(patterns: &[Pattern], haystack: &[u8], expected: &[Location]) {
-        let mut ll = LeftmostLongest::new(patterns);
-        let locations = all(&mut ll, haystack, 0);
-        assert_eq!(expected.len(), locations.len());
-        for (index, expected) in expected.iter().enumerate() {
-            assert_eq!(expected, &locations[index]);
-        }
+    #[test]
+    fn ahocorasick_standard_overlapping() {
+        // "she", "he", and "hers" all occur in "ushers" and overlap ("his" does not occur);
+        // Standard semantics must surface all three rather than collapsing to the longest
+        // per position.
+        let haystack = b"ushers";
+        let patterns = [
+            Pattern { id: 0, value: b"he" },
+            Pattern { id: 1, value: b"she" },
+            Pattern { id: 2, value: b"his" },
+            Pattern { id: 3, value: b"hers" },
+        ];
+        let ac: AhoCorasick = AhoCorasick::try_new(MatchKind::Standard, false, &patterns).unwrap();
+        let pattern_ids: Vec<usize> = ac.find_iter(haystack).map(|l| l.r#match.pattern_id).collect();
+        assert_eq!(pattern_ids, vec![1, 0, 3]);
+    }
+
+    #[test]
+    fn ahocorasick_leftmost_first_prefers_earliest_start_over_insertion_order() {
+        // "he" and "she" both match in "she" ("she"[1..3] == "he"), but they start at
+        // different positions (1 vs 0); true leftmost semantics require the earliest
+        // start to win outright, regardless of insertion order -- insertion order is only
+        // a tie breaker when two patterns would match at the SAME start position.
+        let haystack = b"she";
+        let patterns = [
+            Pattern { id: 0, value: b"he" },
+            Pattern { id: 1, value: b"she" },
+        ];
+        let ac: AhoCorasick = AhoCorasick::try_new(MatchKind::LeftmostFirst, false, &patterns).unwrap();
+        let locations: Vec<Location> = ac.find_iter(haystack).collect();
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].r#match.pattern_id, 1);
+    }
+
+    #[test]
+    fn ahocorasick_leftmost_first_breaks_same_start_ties_by_insertion_order() {
+        // "ab" and "abc" both start at position 0; "ab" is inserted first, so LeftmostFirst
+        // must prefer it even though "abc" is the longer match (LeftmostLongest would
+        // instead prefer "abc").
+        let haystack = b"abc";
+        let patterns = [
+            Pattern { id: 0, value: b"ab" },
+            Pattern { id: 1, value: b"abc" },
+        ];
+        let ac: AhoCorasick = AhoCorasick::try_new(MatchKind::LeftmostFirst, false, &patterns).unwrap();
+        let locations: Vec<Location> = ac.find_iter(haystack).collect();
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].r#match.pattern_id, 0);
     }
 
-    fn all(ll: &mut LeftmostLongest, haystack: &[u8], mut at: usize) -> Vec<Location> {
-        let mut locations: Vec<Location> = Vec::new();
+    #[test]
+    fn dfa_matches_automaton() {
+        let haystack = b"abc def ghi jkl mno pqr abc";
+        let patterns = [
+            Pattern { id: 0, value: b"bc" },
+            Pattern { id: 1, value: b"ghi" },
+            Pattern { id: 2, value: b"o p" },
+            Pattern { id: 3, value: b"qr" },
+        ];
+        let ac: AhoCorasick = AhoCorasick::try_new(MatchKind::LeftmostLongest, false, &patterns).unwrap();
+        let dfa = Dfa::new(&ac);
+
+        let mut at = 0;
         while at < haystack.len() {
-            if let Some(loc) = ll.find(haystack, at) {
-                if loc.end == at {
-                    at += 1;
-                } else {
-                    at = loc.end;
-                }
-                locations.push(loc);
-            } else {
-                break;
+            let from_ac = ac.find(haystack, at);
+            let from_dfa = dfa.find(haystack, at);
+            assert_eq!(from_ac, from_dfa);
+            match from_dfa {
+                Some(loc) if loc.end != at => at = loc.end,
+                Some(_) => at += 1,
+                None => break,
             }
         }
-        locations
+    }
+
+    #[test]
+    fn find_stream_matches_find() {
+        let haystack = b"ushers";
+        let patterns = [
+            Pattern { id: 0, value: b"he" },
+            Pattern { id: 1, value: b"she" },
+            Pattern { id: 2, value: b"his" },
+            Pattern { id: 3, value: b"hers" },
+        ];
+        let ac: AhoCorasick = AhoCorasick::try_new(MatchKind::Standard, false, &patterns).unwrap();
+        let from_find: Vec<usize> = ac.find_iter(haystack).map(|loc| loc.end).collect();
+
+        let from_stream: Vec<usize> = ac
+            .find_stream(std::io::Cursor::new(haystack))
+            .map(|m| m.unwrap().end)
+            .collect();
+
+        assert_eq!(from_find, from_stream);
+    }
+
+    #[test]
+    fn find_stream_matches_find_under_leftmost_longest() {
+        // "ab" is a prefix of "abc"; under LeftmostLongest only the longer match at the
+        // same start should be reported, not both, unlike under Standard semantics where
+        // find_stream_matches_find's haystack happens to make both agree.
+        let haystack = b"abc";
+        let patterns = [
+            Pattern { id: 0, value: b"ab" },
+            Pattern { id: 1, value: b"abc" },
+        ];
+        let ac: AhoCorasick = AhoCorasick::try_new(MatchKind::LeftmostLongest, false, &patterns).unwrap();
+        let from_find: Vec<usize> = ac.find_iter(haystack).map(|loc| loc.end).collect();
+
+        let from_stream: Vec<usize> = ac
+            .find_stream(std::io::Cursor::new(haystack))
+            .map(|m| m.unwrap().end)
+            .collect();
+
+        assert_eq!(from_find, vec![3]);
+        assert_eq!(from_find, from_stream);
+    }
+
+    #[test]
+    fn find_anchored_requires_match_at_start() {
+        let patterns = [
+            Pattern { id: 0, value: b"cat" },
+            Pattern { id: 1, value: b"dog" },
+        ];
+        let ac: AhoCorasick = AhoCorasick::try_new(MatchKind::LeftmostLongest, true, &patterns).unwrap();
+        let haystack = b"xxcat";
+
+        // "cat" does not start at 0, so an anchored search from 0 must not find it
+        // even though an ordinary find would scan ahead and match at index 2.
+        assert_eq!(ac.find_anchored(haystack, 0), None);
+        assert_eq!(
+            ac.find_anchored(haystack, 2),
+            Some(Location { r#match: Match { pattern_id: 0, pattern_len: 3 }, end: 5 }),
+        );
+    }
+
+    #[test]
+    fn find_anchored_rejects_fail_chain_hop_to_a_later_start() {
+        // "x" is a proper suffix of "axc"'s prefix "ax" and also a prefix of "xbc", so the
+        // trie's fail pointer for the "a","x" state points into the "xbc" branch. An
+        // anchored search must not follow that pointer: doing so would report "xbc" as
+        // though it started at 0, when it actually starts at 1.
+        let patterns = [
+            Pattern { id: 0, value: b"axc" },
+            Pattern { id: 1, value: b"xbc" },
+        ];
+        for kind in [MatchKind::Standard, MatchKind::LeftmostFirst, MatchKind::LeftmostLongest] {
+            let ac: AhoCorasick = AhoCorasick::try_new(kind, true, &patterns).unwrap();
+            let haystack = b"axbc";
+            assert_eq!(ac.find_anchored(haystack, 0), None, "kind = {:?}", kind);
+            assert_eq!(
+                ac.find_anchored(haystack, 1),
+                Some(Location { r#match: Match { pattern_id: 1, pattern_len: 3 }, end: 4 }),
+                "kind = {:?}", kind,
+            );
+        }
+    }
+
+    #[test]
+    fn dfa_find_rejects_fail_chain_hop_to_a_later_start() {
+        // Same scenario as find_anchored_rejects_fail_chain_hop_to_a_later_start: Dfa
+        // resolves the same transition table ahead of time, so it must not bake the
+        // fail-chain hop in as an "explicit" transition either.
+        let patterns = [
+            Pattern { id: 0, value: b"axc" },
+            Pattern { id: 1, value: b"xbc" },
+        ];
+        let ac: AhoCorasick = AhoCorasick::try_new(MatchKind::LeftmostLongest, true, &patterns).unwrap();
+        let dfa = Dfa::new(&ac);
+        let haystack = b"axbc";
+        assert_eq!(dfa.find(haystack, 0), None);
+        assert_eq!(
+            dfa.find(haystack, 1),
+            Some(Location { r#match: Match { pattern_id: 1, pattern_len: 3 }, end: 4 }),
+        );
+    }
+
+    #[test]
+    fn try_new_rejects_tries_too_large_for_u8_state_ids() {
+        // These 301 single-byte patterns cover all 256 byte values, so the trie needs 259
+        // states (FAIL, DEAD, START, plus one node per distinct byte), more than
+        // u8::MAX + 1, so a u8-backed automaton must report the overflow
+        // instead of silently wrapping state ids.
+        let values: Vec<[u8; 1]> = (0..=255u16).chain(0..=44).map(|b| [b as u8]).collect();
+        let patterns: Vec<Pattern> = values.iter().enumerate().map(|(id, value)| Pattern { id, value }).collect();
+
+        let err = AhoCorasick::<u8>::try_new(MatchKind::Standard, false, &patterns).unwrap_err();
+        assert_eq!(err.max, 256);
+        assert!(err.len > 256);
+    }
+
+    #[test]
+    fn any_ahocorasick_picks_narrowest_state_id() {
+        let patterns = [
+            Pattern { id: 0, value: b"bc" },
+            Pattern { id: 1, value: b"ghi" },
+        ];
+        let any = AnyAhoCorasick::new(MatchKind::LeftmostLongest, false, &patterns);
+        assert!(matches!(any, AnyAhoCorasick::U8(_)));
+        assert_eq!(
+            any.find(b"abc", 0),
+            Some(Location { r#match: Match { pattern_id: 0, pattern_len: 2 }, end: 3 }),
+        );
+    }
+
+    #[test]
+    fn find_iter_matches_manual_find_loop() {
+        let haystack = b"abc def ghi jkl mno pqr abc";
+        let patterns = [
+            Pattern { id: 0, value: b"bc" },
+            Pattern { id: 1, value: b"ghi" },
+        ];
+        let ac: AhoCorasick = AhoCorasick::try_new(MatchKind::LeftmostLongest, false, &patterns).unwrap();
+
+        let mut from_manual = Vec::new();
+        let mut at = 0;
+        while let Some(loc) = ac.find(haystack, at) {
+            at = if loc.end == at { at + 1 } else { loc.end };
+            from_manual.push(loc);
+        }
+
+        let from_iter: Vec<Location> = ac.find_iter(haystack).collect();
+        assert_eq!(from_manual, from_iter);
+    }
+
+    #[track_caller]
+    fn t(kind: MatchKind, patterns: &[Pattern], haystack: &[u8], expected: &[Location]) {
+        let ac: AhoCorasick = AhoCorasick::try_new(kind, false, patterns).unwrap();
+        let locations: Vec<Location> = ac.find_iter(haystack).collect();
+        assert_eq!(expected.len(), locations.len());
+        for (index, expected) in expected.iter().enumerate() {
+            assert_eq!(expected, &locations[index]);
+        }
     }
 }
@@ -1,15 +1,14 @@
-#![allow(warnings)]
-
 pub mod ahocorasick;
 
 /// Matched pattern.
-#[derive(Clone)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Match {
     pub pattern_id: usize,
     pub pattern_len: usize,
 }
 
 /// Location of a match within some source text.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Location {
     pub r#match: Match,
     /// Index of the first non-pattern byte that is discovered after a match.